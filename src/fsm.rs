@@ -1,6 +1,7 @@
 //!
 //! --- Transition diagram or transition table ---
 
+use crate::DomainLiteralKind;
 use std::str::Chars;
 
 /// FSM is an abstraction over behavior of deterministic finite automata. A DFA has a set of states
@@ -17,7 +18,14 @@ pub trait FSM<S> {
 
 /// The set of possible states in a DFA that represents a language accepting all valid email
 /// addresses. [State::Error] is a dead state (or trap state).
-#[derive(Clone, Debug, Copy)]
+///
+/// The `Cfws*` states only come into play when [`Machine`] is constructed with CFWS enabled; they
+/// are never entered while parsing in strict addr-spec mode. CFWS is only ever entered around a
+/// dot-atom as a whole (before [`State::LocalAtom`]/[`State::DomainAtom`], around `@`, and after
+/// the domain) — never between the `.` and its neighbouring atoms, per RFC 5322 §3.2.3's
+/// `dot-atom-text = 1*atext *("." 1*atext)`, which has no CFWS production at the dot. So
+/// [`State::LocalDot`] and [`State::DomainDot`] are never CFWS anchors.
+#[derive(Clone, Debug, Copy, PartialEq, Eq)]
 pub enum State {
     AddrSpec,
     LocalAtom,
@@ -30,6 +38,16 @@ pub enum State {
     DomainDText,
     DomainDot,
     DomainLiteral,
+    /// Consuming a run of `WSP`, or the gap right after a comment closed.
+    CfwsSpace,
+    /// Just consumed the `CR` of a fold; a `LF` must follow.
+    CfwsFoldCr,
+    /// Just consumed the `LF` of a fold; at least one `WSP` must follow.
+    CfwsFoldLf,
+    /// Inside a `(...)` comment; nesting depth is tracked by [`MachineIterator::comment_depth`].
+    CfwsComment,
+    /// Inside a comment, just consumed `\` of a `quoted-pair`; one more char is expected.
+    CfwsCommentEscape,
     Error,
 }
 
@@ -41,7 +59,18 @@ impl State {
     const AT: char = '@';
     const OPEN_BRACKET: char = '[';
     const CLOSE_BRACKET: char = ']';
-    fn is_atext(c: char) -> bool {
+    const OPEN_PAREN: char = '(';
+    const CLOSE_PAREN: char = ')';
+    const CR: char = '\r';
+    const LF: char = '\n';
+    pub(crate) fn is_wsp(c: char) -> bool {
+        c == ' ' || c == '\t'
+    }
+    fn is_ctext(c: char) -> bool {
+        let n: u32 = c.into();
+        (33 <= n && n <= 39) || (42 <= n && n <= 91) || (93 <= n && n <= 126)
+    }
+    pub(crate) fn is_atext(c: char) -> bool {
         let n: u32 = c.into();
         c == '!'
             || c == '#'
@@ -65,20 +94,40 @@ impl State {
             || (0x61 <= n && n <= 0x7A) // a-z
             || (0x30 <= n && n <= 0x39) // 0-9
     }
-    fn is_qtext(c: char) -> bool {
+    pub(crate) fn is_qtext(c: char) -> bool {
         let n: u32 = c.into();
         n == 33 || (35 <= n && n <= 91) || (93 <= n && n <= 126)
     }
     fn is_dtext(c: char) -> bool {
         let n: u32 = c.into();
-        (33 <= n && n <= 90) && (94 <= n && n <= 126)
+        (33 <= n && n <= 90) || (94 <= n && n <= 126)
     }
-    fn is_escape(c: char) -> bool {
+    pub(crate) fn is_escape(c: char) -> bool {
         let n: u32 = c.into();
         (0x21 <= n && n <= 0x7E) // VCHAR
             || n == 0x20 // SPACE
             || n == 0x09 // HTAB
     }
+
+    /// Maps this state to the human-readable phase of the grammar it represents, for diagnostics
+    /// (see [`crate::Error::InvalidEmail`] and [`crate::Error::IncompleteEmail`]).
+    pub(crate) fn context(&self) -> crate::ErrorContext {
+        use crate::ErrorContext::*;
+        match self {
+            Self::AddrSpec => Start,
+            Self::LocalAtom | Self::LocalDot => LocalAtom,
+            Self::LocalQText | Self::LocalEscape | Self::LocalQString => LocalQuoted,
+            Self::LocalPart | Self::DomainAtom | Self::DomainDot => Domain,
+            Self::DomainDText => DomainLiteral,
+            Self::DomainLiteral => Domain,
+            Self::CfwsSpace
+            | Self::CfwsFoldCr
+            | Self::CfwsFoldLf
+            | Self::CfwsComment
+            | Self::CfwsCommentEscape => Cfws,
+            Self::Error => Start,
+        }
+    }
 }
 
 /// State implements FSM and defines a DFA for language accepting all valid email addresses.
@@ -139,7 +188,10 @@ impl FSM<State> for State {
             Self::DomainLiteral => match c {
                 _ => Self::Error,
             },
-            Self::Error => Self::Error,
+            // The Cfws* states are only ever reached and left via MachineIterator::step_cfws,
+            // which never calls through to this table for them.
+            Self::Error | Self::CfwsSpace | Self::CfwsFoldCr | Self::CfwsFoldLf
+            | Self::CfwsComment | Self::CfwsCommentEscape => Self::Error,
         }
     }
     fn is_final(state: &Self) -> bool {
@@ -166,29 +218,285 @@ impl FSM<State> for State {
 pub struct MachineIterator<'a> {
     input: Chars<'a>,
     state: State,
+    literal_buf: String,
+    literal_kind: Option<DomainLiteralKind>,
+    cfws: bool,
+    /// When set, `atext`/`qtext` accept `UTF8-non-ascii` scalar values (RFC 6531/6532 EAI mode).
+    eai: bool,
+    /// The structural state CFWS was entered from, so that the char which ends the CFWS run can
+    /// be re-dispatched as if CFWS had never been there.
+    resume: State,
+    /// Nesting depth of `(...)` comments; only meaningful while `state` is a `Cfws*` variant.
+    /// Comment nesting is unbounded by the grammar, so this is incremented/decremented with
+    /// saturating arithmetic rather than risking an overflow panic on pathological input.
+    comment_depth: u32,
+    /// The input re-assembled with any CFWS (whitespace, folds, comments) stripped out.
+    canonical: String,
+    /// Count of input chars consumed so far; the position of the next char read.
+    index: usize,
+    /// The position, offending char, and source state of the *first* transition into
+    /// [`State::Error`], if one has happened yet.
+    error: Option<(usize, char, State)>,
 }
 
 /// MachineIterator just wraps over input iterator and performs transitions at every step.
 /// It keeps track of current state as well. Thus, next state is determined using current state as
 /// well as the input symbol based on the transition rules defined.
+///
+/// The grammar for the interior of a `domain-literal` is not regular (it needs arithmetic to
+/// range-check an IPv4 octet or count IPv6 groups), so this iterator also collects the raw text
+/// between `[` and `]` into `literal_buf` and, once `]` is reached, hands it off to
+/// [`crate::literal::validate`]. [`State::DomainLiteral`] is only kept as the resulting state if
+/// that sub-parser accepts the content; otherwise the machine falls back to [`State::Error`].
 impl<'a> Iterator for MachineIterator<'a> {
     type Item = State;
     fn next(&mut self) -> Option<Self::Item> {
         let c = self.input.next()?;
-        self.state = State::transition(self.state, c);
+        let from = self.state;
+        let structural = self.step(c);
+        if self.error.is_none() && from != State::Error && matches!(self.state, State::Error) {
+            self.error = Some((self.index, c, from));
+        }
+        if structural {
+            self.canonical.push(c);
+        }
+        self.index += 1;
         Some(self.state)
     }
 }
 
+impl<'a> MachineIterator<'a> {
+    /// Runs one transition, returning whether `c` is structural content that belongs in the
+    /// canonical, CFWS-stripped form of the address (as opposed to whitespace or a comment).
+    fn step(&mut self, c: char) -> bool {
+        if self.cfws {
+            if let Some(structural) = self.step_cfws(c) {
+                return structural;
+            }
+        }
+        match self.state {
+            State::LocalPart if c == State::OPEN_BRACKET => self.literal_buf.clear(),
+            State::DomainDText if c != State::CLOSE_BRACKET => self.literal_buf.push(c),
+            _ => {}
+        }
+        if let Some(widened) = Self::widen_eai(self.state, self.eai, c) {
+            self.state = widened;
+            return true;
+        }
+        self.state = State::transition(self.state, c);
+        if matches!(self.state, State::DomainLiteral) {
+            self.literal_kind = crate::literal::validate(&self.literal_buf, self.eai);
+            if self.literal_kind.is_none() {
+                self.state = State::Error;
+            }
+        }
+        true
+    }
+
+    /// Handles `c` if the machine is inside CFWS, or if `c` is what diverts a structural state
+    /// into CFWS (CFWS is only reachable at the points the grammar allows it: around the
+    /// local-part, around `@`, and after the domain). [`State::LocalDot`]/[`State::DomainDot`]
+    /// are deliberately absent from the anchor list below — `dot-atom`'s CFWS wraps the atom as
+    /// a whole, not the individual `.` separators between its labels, so CFWS around a dot is
+    /// not part of the grammar. Returns `None` if `c` belongs to the ordinary, non-CFWS
+    /// transition table instead.
+    fn step_cfws(&mut self, c: char) -> Option<bool> {
+        match self.state {
+            State::CfwsComment => {
+                self.advance_comment(c);
+                Some(false)
+            }
+            State::CfwsCommentEscape => {
+                self.state = if State::is_escape(c) {
+                    State::CfwsComment
+                } else {
+                    State::Error
+                };
+                Some(false)
+            }
+            State::CfwsFoldCr => {
+                self.state = if c == State::LF {
+                    State::CfwsFoldLf
+                } else {
+                    State::Error
+                };
+                Some(false)
+            }
+            State::CfwsFoldLf => {
+                self.state = if State::is_wsp(c) {
+                    State::CfwsSpace
+                } else {
+                    State::Error
+                };
+                Some(false)
+            }
+            State::CfwsSpace => {
+                if State::is_wsp(c) {
+                    Some(false)
+                } else if c == State::OPEN_PAREN {
+                    self.comment_depth = 1;
+                    self.state = State::CfwsComment;
+                    Some(false)
+                } else if c == State::CR {
+                    self.state = State::CfwsFoldCr;
+                    Some(false)
+                } else {
+                    if self.resume == State::LocalPart && c == State::OPEN_BRACKET {
+                        self.literal_buf.clear();
+                    }
+                    self.state = Self::exit_cfws(self.resume, c, self.eai);
+                    Some(true)
+                }
+            }
+            anchor @ (State::AddrSpec
+            | State::LocalAtom
+            | State::LocalQString
+            | State::LocalPart
+            | State::DomainAtom
+            | State::DomainLiteral)
+                if State::is_wsp(c) || c == State::CR || c == State::OPEN_PAREN =>
+            {
+                self.resume = anchor;
+                self.state = if c == State::OPEN_PAREN {
+                    self.comment_depth = 1;
+                    State::CfwsComment
+                } else if c == State::CR {
+                    State::CfwsFoldCr
+                } else {
+                    State::CfwsSpace
+                };
+                Some(false)
+            }
+            _ => None,
+        }
+    }
+
+    /// `ccontent = ctext / quoted-pair / comment`, nested to a depth tracked by `comment_depth`.
+    fn advance_comment(&mut self, c: char) {
+        match c {
+            State::OPEN_PAREN => self.comment_depth = self.comment_depth.saturating_add(1),
+            State::CLOSE_PAREN => {
+                self.comment_depth = self.comment_depth.saturating_sub(1);
+                if self.comment_depth == 0 {
+                    self.state = State::CfwsSpace;
+                }
+            }
+            State::BACKSLASH => self.state = State::CfwsCommentEscape,
+            c if State::is_ctext(c) || State::is_wsp(c) => {}
+            _ => self.state = State::Error,
+        }
+    }
+
+    /// Re-dispatches `c` as if the CFWS just consumed had never been there, picking up from the
+    /// structural state it interrupted. A dot-atom's CFWS only wraps the atom as a whole, so
+    /// CFWS after the local-part may only be followed by `@`, and trailing CFWS after the domain
+    /// may not be followed by anything at all.
+    fn exit_cfws(resume: State, c: char, eai: bool) -> State {
+        match resume {
+            State::LocalAtom | State::LocalQString => {
+                if c == State::AT {
+                    State::LocalPart
+                } else {
+                    State::Error
+                }
+            }
+            State::DomainAtom | State::DomainLiteral => State::Error,
+            anchor => Self::widen_eai(anchor, eai, c).unwrap_or_else(|| State::transition(anchor, c)),
+        }
+    }
+
+    /// If EAI mode is on and `c` is a `UTF8-non-ascii` scalar value, returns the state `atext`
+    /// (or `qtext`/`dtext`, inside a quoted-string or domain-literal respectively) widens to;
+    /// otherwise `None`, so the caller falls back to the ordinary ASCII-only transition table.
+    fn widen_eai(state: State, eai: bool, c: char) -> Option<State> {
+        if !eai || (c as u32) < 0x80 {
+            return None;
+        }
+        match state {
+            State::AddrSpec | State::LocalAtom | State::LocalDot => Some(State::LocalAtom),
+            State::LocalQText => Some(State::LocalQText),
+            State::LocalPart | State::DomainAtom | State::DomainDot => Some(State::DomainAtom),
+            State::DomainDText => Some(State::DomainDText),
+            _ => None,
+        }
+    }
+
+    /// The recognized form of the domain literal, once the machine has passed through
+    /// [`State::DomainLiteral`]. `None` until then (or if the email has no domain literal).
+    pub(crate) fn literal_kind(&self) -> Option<DomainLiteralKind> {
+        self.literal_kind
+    }
+
+    /// The address with any CFWS (whitespace, folds, comments) stripped out. Identical to the
+    /// original input when the machine was not constructed with CFWS support, since then no
+    /// character is ever treated as CFWS.
+    pub(crate) fn canonical(&self) -> &str {
+        &self.canonical
+    }
+
+    /// Whether the machine is currently in an accepting state, taking into account that trailing
+    /// CFWS after a complete domain is acceptable, while leading/internal CFWS left dangling
+    /// (e.g. an unterminated comment, or a fold with no content after it) is not.
+    pub(crate) fn is_final(&self) -> bool {
+        match self.state {
+            State::CfwsSpace => matches!(self.resume, State::DomainAtom | State::DomainLiteral),
+            _ => State::is_final(&self.state),
+        }
+    }
+
+    /// The current state, for callers that need to report *why* parsing is stuck here (e.g. when
+    /// input ran out without ever hitting [`State::Error`]).
+    pub(crate) fn state(&self) -> State {
+        self.state
+    }
+
+    /// Count of chars consumed so far.
+    pub(crate) fn position(&self) -> usize {
+        self.index
+    }
+
+    /// The position, offending char, and source state of the first transition into
+    /// [`State::Error`], if parsing ever hit one.
+    pub(crate) fn error(&self) -> Option<(usize, char, State)> {
+        self.error
+    }
+}
+
 /// Machine is the core export of the module. It is an [IntoIterator] and consuming the iterator
 /// determines if given string literal is a valid email address or not.
 pub struct Machine<'a> {
     input: &'a str,
+    cfws: bool,
+    eai: bool,
 }
 
 impl<'a> Machine<'a> {
     pub fn new(s: &'a str) -> Self {
-        Machine { input: s }
+        Machine {
+            input: s,
+            cfws: false,
+            eai: false,
+        }
+    }
+
+    /// Like [`Machine::new`], but also accepts comments and folding white space (CFWS) around
+    /// the local-part, around `@`, and trailing after the domain.
+    pub fn new_with_cfws(s: &'a str) -> Self {
+        Machine {
+            input: s,
+            cfws: true,
+            eai: false,
+        }
+    }
+
+    /// Builds a machine with CFWS and EAI (internationalized `atext`/`qtext`) independently
+    /// toggled; see [`crate::ParseOptions`].
+    pub fn new_with_options(s: &'a str, cfws: bool, eai: bool) -> Self {
+        Machine {
+            input: s,
+            cfws,
+            eai,
+        }
     }
 }
 
@@ -199,6 +507,87 @@ impl<'a> IntoIterator for Machine<'a> {
         MachineIterator {
             state: State::AddrSpec,
             input: self.input.chars(),
+            literal_buf: String::new(),
+            literal_kind: None,
+            cfws: self.cfws,
+            eai: self.eai,
+            resume: State::AddrSpec,
+            comment_depth: 0,
+            canonical: String::new(),
+            index: 0,
+            error: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn accepts_cfws(s: &str) -> bool {
+        let mut iter = Machine::new_with_cfws(s).into_iter();
+        let mut consumed = false;
+        for _ in &mut iter {
+            consumed = true;
+        }
+        consumed && iter.is_final()
+    }
+
+    #[test]
+    fn cfws_wraps_local_part_and_domain() {
+        assert!(accepts_cfws("  john@example.com  "));
+        assert!(accepts_cfws("john (a comment) @example.com"));
+        assert!(accepts_cfws("john@ (nested (comment)) example.com"));
+    }
+
+    #[test]
+    fn cfws_rejects_unterminated_comment() {
+        assert!(!accepts_cfws("john(unterminated@example.com"));
+    }
+
+    #[test]
+    fn deeply_nested_comment_does_not_panic() {
+        // Regression test: `comment_depth` used to be a `u8` incremented with plain `+=`,
+        // which panicked on overflow for 256+ levels of `(` nesting instead of erroring.
+        let input = format!("{}a@example.com", "(".repeat(300));
+        assert!(!accepts_cfws(&input));
+    }
+
+    #[test]
+    fn strict_mode_rejects_cfws() {
+        let mut iter = Machine::new("john@ example.com").into_iter();
+        for _ in &mut iter {}
+        assert!(!iter.is_final());
+    }
+
+    fn accepts_eai(s: &str) -> bool {
+        let mut iter = Machine::new_with_options(s, false, true).into_iter();
+        let mut consumed = false;
+        for _ in &mut iter {
+            consumed = true;
         }
+        consumed && iter.is_final()
+    }
+
+    #[test]
+    fn eai_widens_local_and_domain_atoms() {
+        assert!(accepts_eai("用户@例え.jp"));
+        assert!(!{
+            let mut iter = Machine::new("用户@例え.jp").into_iter();
+            for _ in &mut iter {}
+            iter.is_final()
+        });
+    }
+
+    #[test]
+    fn eai_widens_domain_literal_dtext() {
+        // Regression test: `widen_eai` used to omit `State::DomainDText`, so a non-ASCII
+        // `Standardized-tag:dtext` domain literal was rejected even with EAI enabled.
+        assert!(accepts_eai("user@[x400-gateway:café]"));
+        assert!(!{
+            let mut iter = Machine::new("user@[x400-gateway:café]").into_iter();
+            for _ in &mut iter {}
+            iter.is_final()
+        });
     }
 }
@@ -1,7 +1,13 @@
 //! ## Grammar
 //! Parser for email address (`addr-spec`) as defined in Section 3.4.1 of [`RFC5322`].
-//! This crate implements only a subset of the grammar and does not support folding white space
-//! and comments in email address. Also, the grammar rules that are defined to preserve backwards
+//! By default this crate implements only a subset of the grammar and does not support folding
+//! white space and comments (CFWS) in the email address; pass [`ParseOptions::cfws`] to opt into
+//! CFWS around the local-part, around `@`, and trailing after the domain, which real-world
+//! addresses taken from mail headers commonly carry. CFWS is deliberately *not* accepted between
+//! the labels of a dot-atom (e.g. `john . doe@example.com`): per RFC 5322 §3.2.3,
+//! `dot-atom = [CFWS] dot-atom-text [CFWS]` only wraps the dot-atom as a whole, and
+//! `dot-atom-text = 1*atext *("." 1*atext)` has no CFWS production between the `.` and its
+//! neighbouring atoms. Also, the grammar rules that are defined to preserve backwards
 //! compatibility are not supported. The grammar implemented is described below:
 //!
 //! ```text
@@ -72,18 +78,70 @@
 //! let email: Email = "someone@example.com".parse().unwrap();
 //! ```
 
-use crate::fsm::{State, FSM};
 use std::fmt::{Display, Formatter};
 use std::str::FromStr;
 use thiserror::Error;
 
 /// Email parsing errors.
-#[derive(Error, Debug, Clone)]
+#[derive(Error, Debug, Clone, PartialEq)]
 pub enum Error {
     #[error("cannot parse empty email id")]
     EmptyEmail,
-    #[error("invalid RFC5322 formatted email id")]
-    InvalidEmail,
+    #[error("unexpected {found:?} at position {position} (while reading {context})")]
+    InvalidEmail {
+        /// Char index of the offending character.
+        position: usize,
+        /// The character that could not be consumed.
+        found: char,
+        /// The grammar phase being read when `found` was rejected.
+        context: ErrorContext,
+    },
+    #[error("address ended unexpectedly at position {position} (while reading {context})")]
+    IncompleteEmail {
+        /// Char index at which input ran out.
+        position: usize,
+        /// The grammar phase being read when input ran out.
+        context: ErrorContext,
+    },
+    #[error("domain is not IDNA-convertible: {0}")]
+    Idna(String),
+    #[error("invalid RFC5322 mailbox: {0}")]
+    InvalidMailbox(String),
+    #[error("local-part character {0:?} is not representable in a quoted-string (not VCHAR, SPACE or HTAB)")]
+    UnencodableLocalPart(char),
+}
+
+/// Which phase of the `addr-spec` grammar was being read when parsing failed, attached to
+/// [`Error::InvalidEmail`] and [`Error::IncompleteEmail`] so callers can build actionable
+/// form-validation feedback.
+#[derive(Clone, Debug, Copy, PartialEq, Eq)]
+pub enum ErrorContext {
+    /// Before any local-part character has been read.
+    Start,
+    /// An unquoted local-part atom.
+    LocalAtom,
+    /// A quoted local-part (`"..."`).
+    LocalQuoted,
+    /// A DNS domain label.
+    Domain,
+    /// The interior of a domain literal (`[...]`).
+    DomainLiteral,
+    /// Whitespace or a comment (CFWS).
+    Cfws,
+}
+
+impl Display for ErrorContext {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let phase = match self {
+            Self::Start => "the start of the address",
+            Self::LocalAtom => "local atom",
+            Self::LocalQuoted => "quoted string",
+            Self::Domain => "domain",
+            Self::DomainLiteral => "domain literal",
+            Self::Cfws => "whitespace or comment",
+        };
+        write!(f, "{phase}")
+    }
 }
 
 /// Email parsing is accomplished using a finite state machine. FSM is defined in this module.
@@ -91,26 +149,216 @@ pub enum Error {
 /// the state is a final state, then given string is valid email address.
 mod fsm;
 
+/// Validation of the address literals (`[...]`) a domain may carry. The content inside the
+/// brackets is not a regular language, so it is checked by a dedicated sub-parser rather than
+/// more FSM states; see the module docs for why.
+mod literal;
+
+/// Parsing of full `mailbox` syntax (an optional display-name plus an `addr-spec`, the latter
+/// optionally wrapped in angle brackets), as opposed to [`Email`]'s bare `addr-spec`.
+mod mailbox;
+pub use mailbox::Mailbox;
+
+/// The recognized forms of a `domain-literal`, per RFC 5321 §4.1.3.
+#[derive(Clone, Debug, Copy, PartialEq, Eq)]
+pub enum DomainLiteralKind {
+    /// `[192.0.2.1]`
+    Ipv4,
+    /// `[IPv6:2001:db8::1]`
+    Ipv6,
+    /// `[x400-gateway:...]`, any other standardized tag.
+    General,
+}
+
+/// Options controlling how permissive [`Email`] parsing is. The default is strict addr-spec
+/// parsing, matching [`Email::from_str`].
+#[derive(Clone, Debug, Copy, Default)]
+pub struct ParseOptions {
+    cfws: bool,
+    eai: bool,
+}
+
+impl ParseOptions {
+    /// Strict addr-spec parsing, same as the [`Default`] impl.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Accept CFWS (comments and folding white space) around the local-part, around `@`, and
+    /// trailing after the domain, as real-world mail headers commonly carry.
+    pub fn cfws(mut self, enabled: bool) -> Self {
+        self.cfws = enabled;
+        self
+    }
+
+    /// Accept internationalized (EAI/SMTPUTF8, RFC 6531/6532) addresses, widening `atext` and
+    /// `qtext` to accept any `UTF8-non-ascii` scalar value, e.g. `用户@例え.jp`.
+    pub fn eai(mut self, enabled: bool) -> Self {
+        self.eai = enabled;
+        self
+    }
+}
+
 /// This is the core of the crate. Defines email address type which can be constructed by parsing a
 /// string literal. As long as it is constructed properly, then it means the email address is valid.
 pub struct Email {
     local: String,
     domain: String,
+    domain_literal: Option<DomainLiteralKind>,
+    canonical: String,
+}
+
+impl Email {
+    /// Parses `s` according to `options`. [`Email::from_str`] is equivalent to
+    /// `Email::parse_with(s, ParseOptions::new())`.
+    pub fn parse_with(s: &str, options: ParseOptions) -> Result<Self, Error> {
+        let m = fsm::Machine::new_with_options(s, options.cfws, options.eai);
+        let mut iter = m.into_iter();
+        let mut consumed = false;
+        for _ in &mut iter {
+            consumed = true;
+        }
+        if !consumed {
+            return Err(Error::EmptyEmail);
+        }
+        if !iter.is_final() {
+            return Err(match iter.error() {
+                Some((position, found, from)) => Error::InvalidEmail {
+                    position,
+                    found,
+                    context: from.context(),
+                },
+                None => Error::IncompleteEmail {
+                    position: iter.position(),
+                    context: iter.state().context(),
+                },
+            });
+        }
+        let canonical = iter.canonical().to_owned();
+        // `is_final` guarantees the address reached a final state, which is only possible after
+        // consuming an '@'. A quoted local-part may itself contain '@', so in that case the
+        // separator is the '@' right after the closing (unescaped) quote.
+        let at = match canonical.strip_prefix('"') {
+            Some(rest) => {
+                let mut escaped = false;
+                let close = rest.find(|c: char| {
+                    let closes = !escaped && c == '"';
+                    escaped = !escaped && c == '\\';
+                    closes
+                });
+                close.unwrap() + 2
+            }
+            None => canonical.find('@').unwrap(),
+        };
+        let (one, two) = (&canonical[..at], &canonical[at + 1..]);
+        let local = one.to_owned();
+        let domain = two.to_owned();
+        Ok(Self {
+            local,
+            domain,
+            domain_literal: iter.literal_kind(),
+            canonical,
+        })
+    }
+
+    /// The recognized form of the domain, if it was written as an address literal (`[...]`)
+    /// rather than a DNS domain name.
+    pub fn domain_literal(&self) -> Option<DomainLiteralKind> {
+        self.domain_literal
+    }
+
+    /// The address with any CFWS (whitespace, folds, comments) stripped out. Identical to the
+    /// input that was parsed when it carried no CFWS.
+    pub fn canonical(&self) -> &str {
+        &self.canonical
+    }
+
+    /// The raw local-part, exactly as written (quotes and escapes included, if any).
+    pub(crate) fn local(&self) -> &str {
+        &self.local
+    }
+
+    /// The domain, exactly as written.
+    pub(crate) fn domain(&self) -> &str {
+        &self.domain
+    }
+
+    /// The semantic value of the local-part: quotes stripped and `\X` quoted-pairs collapsed to
+    /// `X`. A dot-atom local-part has no escaping to undo and is returned unchanged. Two
+    /// addresses with the same [`decoded_local`](Email::decoded_local) and domain refer to the
+    /// same mailbox even if one was written as a `quoted-string` and the other as a `dot-atom`.
+    pub fn decoded_local(&self) -> String {
+        let Some(inner) = self.local.strip_prefix('"').and_then(|s| s.strip_suffix('"')) else {
+            return self.local.clone();
+        };
+        let mut decoded = String::with_capacity(inner.len());
+        let mut chars = inner.chars();
+        while let Some(c) = chars.next() {
+            if c == '\\' {
+                if let Some(escaped) = chars.next() {
+                    decoded.push(escaped);
+                    continue;
+                }
+            }
+            decoded.push(c);
+        }
+        decoded
+    }
+
+    /// Encodes `local` — an arbitrary, unescaped local-part value — into its minimal RFC 5322
+    /// representation: a bare `dot-atom` when every character of every dot-separated label is
+    /// `atext`, otherwise a `quoted-string`. Inside the `quoted-string`, a `qtext` character is
+    /// copied through as-is; anything else (`"`, `\`, SPACE, HTAB) is backslash-escaped as a
+    /// `quoted-pair`. A true control byte can be neither raw `qtext` nor the target of a
+    /// `quoted-pair` (`quoted-pair`'s `ESCAPE = VCHAR / WSP` does not cover it), so such input is
+    /// rejected with [`Error::UnencodableLocalPart`] rather than silently producing a
+    /// `quoted-string` that [`Email::from_str`] would itself refuse to parse back. The inverse of
+    /// [`Email::decoded_local`].
+    pub fn encode_local(local: &str) -> Result<String, Error> {
+        let is_dot_atom = !local.is_empty()
+            && local
+                .split('.')
+                .all(|label| !label.is_empty() && label.chars().all(fsm::State::is_atext));
+        if is_dot_atom {
+            return Ok(local.to_owned());
+        }
+        let mut encoded = String::with_capacity(local.len() + 2);
+        encoded.push('"');
+        for c in local.chars() {
+            if fsm::State::is_qtext(c) {
+                encoded.push(c);
+            } else if fsm::State::is_escape(c) {
+                encoded.push('\\');
+                encoded.push(c);
+            } else {
+                return Err(Error::UnencodableLocalPart(c));
+            }
+        }
+        encoded.push('"');
+        Ok(encoded)
+    }
+
+    /// Whether the local-part or domain carries any non-ASCII character, i.e. this address could
+    /// only have been parsed with [`ParseOptions::eai`] turned on. The local-part cannot be
+    /// punycoded (it is not a domain label), so it stays UTF-8; see [`Email::ascii_domain`] for
+    /// an SMTP-transmittable form of the domain alone.
+    pub fn is_internationalized(&self) -> bool {
+        self.local.chars().any(|c| !c.is_ascii()) || self.domain.chars().any(|c| !c.is_ascii())
+    }
+
+    /// The domain converted to its ASCII-compatible (Punycode/IDNA) form, suitable for handing to
+    /// an SMTP server that does not speak SMTPUTF8. An already-ASCII domain is returned unchanged
+    /// apart from IDNA's case mapping, which lowercases ASCII letters.
+    pub fn ascii_domain(&self) -> Result<String, Error> {
+        idna::domain_to_ascii(&self.domain).map_err(|e| Error::Idna(format!("{e:?}")))
+    }
 }
 
 /// Support parsing from string literal.
 impl FromStr for Email {
     type Err = Error;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let m = fsm::Machine::new(s);
-        let ref state = m.into_iter().last().ok_or(Error::EmptyEmail)?;
-        let (one, two) = State::is_final(state)
-            .then(|| s.split_once('@').unwrap())
-            .ok_or(Error::InvalidEmail)?;
-        Ok(Self {
-            local: one.to_owned(),
-            domain: two.to_owned(),
-        })
+        Self::parse_with(s, ParseOptions::new())
     }
 }
 
@@ -120,3 +368,85 @@ impl Display for Email {
         writeln!(f, "{}@{}", self.local, self.domain)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_err(s: &str) -> Error {
+        match s.parse::<Email>() {
+            Ok(_) => panic!("expected {s:?} to be rejected"),
+            Err(e) => e,
+        }
+    }
+
+    #[test]
+    fn invalid_email_reports_offending_char_and_context() {
+        assert_eq!(
+            parse_err("john doe@example.com"),
+            Error::InvalidEmail {
+                position: 4,
+                found: ' ',
+                context: ErrorContext::LocalAtom,
+            }
+        );
+    }
+
+    #[test]
+    fn incomplete_email_reports_position_and_context() {
+        assert_eq!(
+            parse_err("john."),
+            Error::IncompleteEmail {
+                position: 5,
+                context: ErrorContext::LocalAtom,
+            }
+        );
+    }
+
+    #[test]
+    fn empty_email_is_its_own_error() {
+        assert_eq!(parse_err(""), Error::EmptyEmail);
+    }
+
+    #[test]
+    fn decoded_local_strips_quotes_and_escapes() {
+        let email: Email = r#""a\"b\\c"@x.com"#.parse().unwrap();
+        assert_eq!(email.decoded_local(), "a\"b\\c");
+    }
+
+    #[test]
+    fn decoded_local_is_unchanged_for_dot_atom() {
+        let email: Email = "john.doe@x.com".parse().unwrap();
+        assert_eq!(email.decoded_local(), "john.doe");
+    }
+
+    #[test]
+    fn encode_local_prefers_bare_dot_atom() {
+        assert_eq!(Email::encode_local("john.doe").unwrap(), "john.doe");
+    }
+
+    #[test]
+    fn encode_local_quotes_and_escapes_specials() {
+        assert_eq!(Email::encode_local("a\"b\\c").unwrap(), r#""a\"b\\c""#);
+    }
+
+    #[test]
+    fn encode_local_round_trips_through_decoded_local() {
+        let local = "a\"b\\c d";
+        let encoded = Email::encode_local(local).unwrap();
+        let email: Email = format!("{encoded}@x.com").parse().unwrap();
+        assert_eq!(email.decoded_local(), local);
+    }
+
+    #[test]
+    fn encode_local_rejects_true_control_bytes() {
+        // Regression test: `encode_local` used to only backslash-escape `"` and `\`, silently
+        // emitting a `quoted-string` containing a raw control byte that `Email::from_str` itself
+        // would then refuse to parse back (quoted-pair's `ESCAPE = VCHAR / WSP` cannot represent
+        // it either).
+        assert_eq!(
+            Email::encode_local("a\u{1}b").unwrap_err(),
+            Error::UnencodableLocalPart('\u{1}')
+        );
+    }
+}
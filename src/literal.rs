@@ -0,0 +1,164 @@
+//! Validation of RFC 5321 §4.1.3 address literals, i.e. the content found inside the
+//! `[` / `]` brackets of a `domain-literal`. The grammar for this region is not regular
+//! (IPv6 group counting and octet range-checks need real arithmetic), so instead of folding
+//! it into [`crate::fsm`] as more DFA states, it is validated by this dedicated sub-parser
+//! once the bracketed text has been collected.
+
+use crate::DomainLiteralKind;
+
+/// Classifies the text between `[` and `]` as one of the three alternatives permitted by
+/// `addr-spec`'s `domain-literal` production, or returns [`None`] if it matches none of them.
+/// `eai` widens the `General` alternative's `dtext` to also accept `UTF8-non-ascii` scalar
+/// values (RFC 6531/6532); `Ipv4`/`Ipv6` are inherently ASCII and are unaffected by it.
+pub(crate) fn validate(s: &str, eai: bool) -> Option<DomainLiteralKind> {
+    if is_ipv4(s) {
+        Some(DomainLiteralKind::Ipv4)
+    } else if let Some(rest) = strip_ipv6_tag(s) {
+        is_ipv6(rest).then_some(DomainLiteralKind::Ipv6)
+    } else if is_general(s, eai) {
+        Some(DomainLiteralKind::General)
+    } else {
+        None
+    }
+}
+
+/// `Snum "." Snum "." Snum "." Snum`, `Snum` being 1-3 digits whose value is at most 255.
+fn is_ipv4(s: &str) -> bool {
+    let parts: Vec<&str> = s.split('.').collect();
+    parts.len() == 4 && parts.iter().all(|p| is_snum(p))
+}
+
+fn is_snum(s: &str) -> bool {
+    (1..=3).contains(&s.len())
+        && s.chars().all(|c| c.is_ascii_digit())
+        && s.parse::<u16>().is_ok_and(|n| n <= 255)
+}
+
+/// Strips a case-insensitive `"IPv6:"` tag, the way the grammar introduces an IPv6 literal.
+/// Uses [`str::get`] rather than direct indexing: in EAI mode `s` may contain multibyte UTF-8
+/// (e.g. a non-IPv6 `General` literal), and a fixed byte-length slice can otherwise land inside
+/// a char and panic.
+fn strip_ipv6_tag(s: &str) -> Option<&str> {
+    let tag_len = "IPv6:".len();
+    s.get(..tag_len)
+        .filter(|t| t.eq_ignore_ascii_case("IPv6:"))
+        .map(|_| &s[tag_len..])
+}
+
+/// 8 groups of 1-4 hex digits, or fewer groups with a single `::` compression, optionally
+/// ending in an embedded IPv4 literal (which counts as two groups).
+fn is_ipv6(s: &str) -> bool {
+    if s.matches("::").count() > 1 {
+        return false;
+    }
+    if let Some(idx) = s.find("::") {
+        let head = group_count(&split_groups(&s[..idx]));
+        let tail = group_count(&split_groups(&s[idx + 2..]));
+        matches!((head, tail), (Some(h), Some(t)) if h + t < 8)
+    } else {
+        group_count(&split_groups(s)) == Some(8)
+    }
+}
+
+fn split_groups(s: &str) -> Vec<&str> {
+    if s.is_empty() {
+        Vec::new()
+    } else {
+        s.split(':').collect()
+    }
+}
+
+/// Counts the number of 16-bit groups represented by `groups`, treating a trailing embedded
+/// IPv4 literal as worth two groups. Returns [`None`] if any group is malformed.
+fn group_count(groups: &[&str]) -> Option<usize> {
+    match groups.split_last() {
+        Some((last, rest)) if is_ipv4(last) => {
+            rest.iter().all(|g| is_hex_group(g)).then(|| rest.len() + 2)
+        }
+        _ => groups.iter().all(|g| is_hex_group(g)).then(|| groups.len()),
+    }
+}
+
+fn is_hex_group(s: &str) -> bool {
+    (1..=4).contains(&s.len()) && s.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// `Standardized-tag ":" 1*dtext`, `Standardized-tag` being an `ldh-str`.
+fn is_general(s: &str, eai: bool) -> bool {
+    match s.split_once(':') {
+        Some((tag, content)) => {
+            is_ldh(tag)
+                && !content.is_empty()
+                && content.chars().all(|c| is_dtext(c) || (eai && !c.is_ascii()))
+        }
+        None => false,
+    }
+}
+
+fn is_ldh(s: &str) -> bool {
+    !s.is_empty()
+        && !s.starts_with('-')
+        && !s.ends_with('-')
+        && s.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+}
+
+fn is_dtext(c: char) -> bool {
+    let n = c as u32;
+    (33..=90).contains(&n) || (94..=126).contains(&n)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classifies_ipv4() {
+        assert_eq!(validate("192.0.2.1", false), Some(DomainLiteralKind::Ipv4));
+        assert_eq!(validate("256.0.2.1", false), None);
+        assert_eq!(validate("1.2.3", false), None);
+    }
+
+    #[test]
+    fn classifies_ipv6() {
+        assert_eq!(
+            validate("IPv6:2001:db8::1", false),
+            Some(DomainLiteralKind::Ipv6)
+        );
+        assert_eq!(
+            validate("IPv6:2001:db8:0:0:0:0:0:1", false),
+            Some(DomainLiteralKind::Ipv6)
+        );
+        assert_eq!(
+            validate("IPv6:::ffff:192.0.2.1", false),
+            Some(DomainLiteralKind::Ipv6)
+        );
+        assert_eq!(validate("IPv6:1::2::3", false), None);
+    }
+
+    #[test]
+    fn classifies_general() {
+        assert_eq!(
+            validate("x400-gateway:foo", false),
+            Some(DomainLiteralKind::General)
+        );
+        assert_eq!(validate("-bad:foo", false), None);
+        assert_eq!(validate("tag:", false), None);
+    }
+
+    #[test]
+    fn general_dtext_is_ascii_only_unless_eai() {
+        assert_eq!(validate("tag:café", false), None);
+        assert_eq!(
+            validate("tag:café", true),
+            Some(DomainLiteralKind::General)
+        );
+    }
+
+    #[test]
+    fn non_ascii_content_with_multibyte_char_at_ipv6_tag_boundary_does_not_panic() {
+        // Regression test: `strip_ipv6_tag` used to slice `s` at a fixed byte offset without
+        // checking for a char boundary, so a non-IPv6 literal with a multibyte UTF-8 char
+        // straddling that offset panicked instead of falling through to `is_general`.
+        assert_eq!(validate("日本語:x", true), None);
+    }
+}
@@ -0,0 +1,242 @@
+//! Parsing of the `mailbox` production (RFC 5322 §3.4), i.e. an `addr-spec` optionally preceded
+//! by a display-name and wrapped in angle brackets:
+//!
+//! ```text
+//! mailbox      =  name-addr / addr-spec
+//! name-addr    =  [display-name] angle-addr
+//! angle-addr   =  [CFWS] "<" addr-spec ">" [CFWS]
+//! display-name =  phrase
+//! phrase       =  1*word
+//! word         =  atom / quoted-string
+//! ```
+//!
+//! [`crate::fsm`] only ever drives the `addr-spec` itself, so `display-name` and the `<`/`>`
+//! bracketing are parsed by hand here, reusing the `atext`/`qtext`/quoted-pair character classes
+//! [`crate::fsm::State`] already defines.
+
+use crate::fsm::State;
+use crate::{Email, Error};
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+/// A parsed `mailbox`: an [`Email`] plus the optional display-name that introduced it, e.g.
+/// `"John Doe" <john@example.com>` or bare `jane@example.com`.
+pub struct Mailbox {
+    name: Option<String>,
+    email: Email,
+}
+
+impl Mailbox {
+    /// The address. Whitespace around the angle-addr has already been trimmed; comments around it
+    /// are not accepted, and the addr-spec itself is parsed in strict mode.
+    pub fn email(&self) -> &Email {
+        &self.email
+    }
+
+    /// The decoded display-name (quotes and escapes removed), if one was present.
+    pub fn display_name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+}
+
+/// Support parsing from string literal.
+impl FromStr for Mailbox {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match find_angle_addr(s) {
+            Some((lt, gt)) => {
+                let name = s[..lt].trim();
+                let trailing = s[gt + 1..].trim();
+                if !trailing.is_empty() {
+                    return Err(Error::InvalidMailbox(format!(
+                        "unexpected text {trailing:?} after angle-addr"
+                    )));
+                }
+                let name = if name.is_empty() {
+                    None
+                } else {
+                    Some(parse_phrase(name)?)
+                };
+                let email = Email::from_str(&s[lt + 1..gt])?;
+                Ok(Self { name, email })
+            }
+            None => Ok(Self {
+                name: None,
+                email: Email::from_str(s)?,
+            }),
+        }
+    }
+}
+
+/// Finds the first unquoted `<` and its matching unquoted `>`, tracking quoting state the whole
+/// way through (both before `<`, in the display-name, and after it, inside the `addr-spec`
+/// itself) so that a literal `<`/`>` inside a quoted-string — display-name or local-part — is
+/// never mistaken for angle-addr bracketing.
+fn find_angle_addr(s: &str) -> Option<(usize, usize)> {
+    let mut quoted = false;
+    let mut chars = s.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '\\' if quoted => {
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            '<' if !quoted => return find_closing_angle(i, &mut chars),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Continues scanning from just after the `<` at `open`, tracking quoting state, for the first
+/// unquoted `>`.
+fn find_closing_angle(
+    open: usize,
+    chars: &mut std::iter::Peekable<std::str::CharIndices>,
+) -> Option<(usize, usize)> {
+    let mut quoted = false;
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '\\' if quoted => {
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            '>' if !quoted => return Some((open, i)),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Parses a `display-name`, decoding any quoted-string word (quotes stripped, `\X` collapsed to
+/// `X`) and joining words with a single space.
+fn parse_phrase(s: &str) -> Result<String, Error> {
+    let mut chars = s.chars().peekable();
+    let mut words = Vec::new();
+    loop {
+        skip_wsp(&mut chars);
+        match chars.peek() {
+            None => break,
+            Some('"') => {
+                chars.next();
+                words.push(parse_quoted_word(&mut chars)?);
+            }
+            Some(&c) if State::is_atext(c) => {
+                let mut word = String::new();
+                while let Some(&c) = chars.peek() {
+                    if !State::is_atext(c) {
+                        break;
+                    }
+                    word.push(c);
+                    chars.next();
+                }
+                words.push(word);
+            }
+            Some(&c) => {
+                return Err(Error::InvalidMailbox(format!(
+                    "unexpected {c:?} in display-name"
+                )))
+            }
+        }
+    }
+    Ok(words.join(" "))
+}
+
+fn parse_quoted_word(chars: &mut std::iter::Peekable<std::str::Chars>) -> Result<String, Error> {
+    let mut word = String::new();
+    loop {
+        match chars.next() {
+            Some('"') => return Ok(word),
+            Some('\\') => match chars.next() {
+                Some(c) if State::is_escape(c) => word.push(c),
+                _ => return Err(Error::InvalidMailbox("dangling quoted-pair".to_owned())),
+            },
+            Some(c) if State::is_qtext(c) || State::is_wsp(c) => word.push(c),
+            Some(c) => {
+                return Err(Error::InvalidMailbox(format!(
+                    "unexpected {c:?} in quoted display-name"
+                )))
+            }
+            None => return Err(Error::InvalidMailbox("unterminated quoted-string".to_owned())),
+        }
+    }
+}
+
+fn skip_wsp(chars: &mut std::iter::Peekable<std::str::Chars>) {
+    while matches!(chars.peek(), Some(&c) if State::is_wsp(c)) {
+        chars.next();
+    }
+}
+
+/// Support formatted output. The display-name is only wrapped in `DQUOTE`s (with `"` and `\`
+/// backslash-escaped) when it contains a character outside `atext`/space, mirroring how a
+/// `dot-atom` local-part is preferred over a `quoted-string` whenever it is sufficient.
+impl Display for Mailbox {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if let Some(name) = &self.name {
+            if name.chars().all(|c| State::is_atext(c) || c == ' ') {
+                write!(f, "{name} <{}@{}>", self.email.local(), self.email.domain())
+            } else {
+                write!(f, "\"")?;
+                for c in name.chars() {
+                    if c == '"' || c == '\\' {
+                        write!(f, "\\")?;
+                    }
+                    write!(f, "{c}")?;
+                }
+                write!(f, "\" <{}@{}>", self.email.local(), self.email.domain())
+            }
+        } else {
+            write!(f, "{}@{}", self.email.local(), self.email.domain())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_err(s: &str) -> Error {
+        match s.parse::<Mailbox>() {
+            Ok(_) => panic!("expected {s:?} to be rejected"),
+            Err(e) => e,
+        }
+    }
+
+    #[test]
+    fn bare_addr_spec_has_no_display_name() {
+        let mbox: Mailbox = "jane@example.com".parse().unwrap();
+        assert_eq!(mbox.display_name(), None);
+        assert_eq!(mbox.email().canonical(), "jane@example.com");
+    }
+
+    #[test]
+    fn name_addr_with_unquoted_display_name() {
+        let mbox: Mailbox = "John Doe <john@example.com>".parse().unwrap();
+        assert_eq!(mbox.display_name(), Some("John Doe"));
+        assert_eq!(mbox.email().canonical(), "john@example.com");
+    }
+
+    #[test]
+    fn name_addr_with_quoted_display_name() {
+        let mbox: Mailbox = r#""Doe, John" <john@example.com>"#.parse().unwrap();
+        assert_eq!(mbox.display_name(), Some("Doe, John"));
+    }
+
+    #[test]
+    fn quoted_local_part_containing_angle_bracket() {
+        // Regression test: `find_angle_addr` used to look for the closing `>` with a naive
+        // substring search, so a quoted local-part containing `>` (legal `qtext`) truncated the
+        // match and left the real tail reported as trailing garbage.
+        let mbox: Mailbox = r#"<"a>b"@x.com>"#.parse().unwrap();
+        assert_eq!(mbox.email().canonical(), r#""a>b"@x.com"#);
+    }
+
+    #[test]
+    fn rejects_trailing_text_after_angle_addr() {
+        assert!(matches!(
+            parse_err("John Doe <john@example.com> extra"),
+            Error::InvalidMailbox(_)
+        ));
+    }
+}